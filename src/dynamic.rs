@@ -0,0 +1,253 @@
+//! Patterns built at runtime from a string, for when you don't know `SIZE` ahead of time
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{matches_masked, pick_rare_byte};
+
+/// Error returned by [`DynPattern::try_from_str`] for a malformed pattern string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternError {
+    /// The pattern string was empty
+    Empty,
+    /// A byte group was neither `??` nor exactly two hex digits
+    BadGroupLength,
+    /// A byte group contained a non-hex-digit character
+    InvalidHex,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            PatternError::Empty => "pattern string was empty",
+            PatternError::BadGroupLength => "pattern byte group was not two hex digits or `??`",
+            PatternError::InvalidHex => "pattern byte group contained a non-hex-digit character",
+        };
+        f.write_str(msg)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PatternError {}
+
+/// Like [`Pattern`](crate::Pattern) but heap-backed, for sizes only known at runtime
+#[derive(Debug)]
+pub struct DynPattern {
+    data: Vec<u8>,
+    mask: Vec<u8>,
+    no_mask: bool,
+    rare_byte: u8,
+    rare_off: usize,
+    has_rare: bool,
+}
+
+impl DynPattern {
+    /// Parse a pattern from a string, like `FF D8 00 03`, each byte encoded in base16
+    /// like ida patterns, `??` matches any byte. Errors instead of panicking on bad input.
+    pub fn try_from_str(sus: &str) -> Result<Self, PatternError> {
+        if sus.trim().is_empty() {
+            return Err(PatternError::Empty);
+        }
+        let mut data = Vec::new();
+        let mut mask = Vec::new();
+        let mut no_mask = true;
+        for group in sus.split_whitespace() {
+            if group == "??" {
+                data.push(0);
+                mask.push(0);
+                no_mask = false;
+            } else {
+                if group.len() != 2 {
+                    return Err(PatternError::BadGroupLength);
+                }
+                if !group.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return Err(PatternError::InvalidHex);
+                }
+                let byte = u8::from_str_radix(group, 16).map_err(|_| PatternError::InvalidHex)?;
+                data.push(byte);
+                mask.push(u8::MAX);
+            }
+        }
+        let (rare_byte, rare_off, has_rare) = pick_rare_byte(&data, &mask);
+        Ok(Self {
+            data,
+            mask,
+            no_mask,
+            rare_byte,
+            rare_off,
+            has_rare,
+        })
+    }
+
+    /// The pattern's concrete data bytes, including placeholder `0`s at wildcard positions
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The pattern's mask, `0` at wildcard positions and `u8::MAX` elsewhere
+    pub(crate) fn mask(&self) -> &[u8] {
+        &self.mask
+    }
+
+    /// Whether every position in the pattern is concrete (no wildcards)
+    pub(crate) fn is_no_mask(&self) -> bool {
+        self.no_mask
+    }
+
+    /// Whether the pattern has at least one concrete byte to prefilter on
+    pub(crate) fn has_rare(&self) -> bool {
+        self.has_rare
+    }
+
+    /// The rarest concrete byte in the pattern
+    pub(crate) fn rare_byte(&self) -> u8 {
+        self.rare_byte
+    }
+
+    /// Offset of `rare_byte` within the pattern
+    pub(crate) fn rare_off(&self) -> usize {
+        self.rare_off
+    }
+
+    /// Search pattern inside bytes
+    pub fn search(&self, bytes: &[u8]) -> Option<usize> {
+        #[cfg(feature = "memchr")]
+        if self.no_mask {
+            return memchr::memmem::find(bytes, &self.data);
+        }
+        #[cfg(feature = "memchr")]
+        if self.has_rare {
+            return self.search_with_prefilter(bytes);
+        }
+        bytes
+            .windows(self.data.len())
+            .position(|slice| matches_masked(&self.data, &self.mask, slice))
+    }
+
+    /// Search using a rare-byte prefilter instead of checking every window
+    #[cfg(feature = "memchr")]
+    fn search_with_prefilter(&self, bytes: &[u8]) -> Option<usize> {
+        for h in memchr::memchr_iter(self.rare_byte, bytes) {
+            if h < self.rare_off {
+                continue;
+            }
+            let start = h - self.rare_off;
+            if start + self.data.len() > bytes.len() {
+                continue;
+            }
+            if matches_masked(&self.data, &self.mask, &bytes[start..start + self.data.len()]) {
+                return Some(start);
+            }
+        }
+        None
+    }
+
+    /// Search pattern inside bytes, returning the offset of the *last* match
+    pub fn rsearch(&self, bytes: &[u8]) -> Option<usize> {
+        #[cfg(feature = "memchr")]
+        if self.no_mask {
+            return memchr::memmem::rfind(bytes, &self.data);
+        }
+        bytes
+            .windows(self.data.len())
+            .enumerate()
+            .rev()
+            .find(|(_, slice)| matches_masked(&self.data, &self.mask, slice))
+            .map(|(i, _)| i)
+    }
+
+    /// Search pattern inside bytes with SIMD
+    #[inline(never)]
+    #[cfg(feature = "simd")]
+    pub fn simd_search(&self, bytes: &[u8]) -> Option<usize> {
+        use wide::u8x16;
+        #[cfg(feature = "memchr")]
+        if self.no_mask {
+            return memchr::memmem::find(bytes, &self.data);
+        }
+        let pattern_chunks = self.data.chunks_exact(16);
+        let mask_chunks = self.mask.chunks_exact(16);
+        'search: for (i, slice) in bytes.windows(self.data.len()).enumerate() {
+            let slice_chunks = slice.chunks_exact(16);
+            let mut pchunks = pattern_chunks.clone();
+            let mut mchunks = mask_chunks.clone();
+            for chunk in slice_chunks {
+                let chunk = u8x16::new(chunk.try_into().unwrap());
+                let pattern_chunk = u8x16::new(pchunks.next()?.try_into().unwrap());
+                let mask_chunk = u8x16::new(mchunks.next()?.try_into().unwrap());
+                let masked = chunk & mask_chunk;
+                if masked != pattern_chunk {
+                    continue 'search;
+                }
+            }
+            let rem_chunk = slice.chunks_exact(16).remainder();
+            let rem_start = slice.len() - rem_chunk.len();
+            'remainder: for (i, byte) in rem_chunk.iter().enumerate() {
+                if self.mask[rem_start + i] == 0 {
+                    continue 'remainder;
+                }
+                if self.data[rem_start + i] != *byte {
+                    continue 'search;
+                }
+            }
+            return Some(i);
+        }
+        None
+    }
+
+    /// Search pattern inside bytes with multiple threads
+    #[cfg(feature = "multithreading")]
+    pub fn par_search(&self, bytes: &[u8]) -> Option<usize> {
+        #[cfg(feature = "memchr")]
+        if self.no_mask {
+            return memchr::memmem::find(bytes, &self.data);
+        }
+        use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+        use rayon::slice::ParallelSlice;
+        let data = &self.data;
+        let mask = &self.mask;
+        bytes
+            .par_windows(data.len())
+            .enumerate()
+            .map(|(i, slice)| matches_masked(data, mask, slice).then_some(i))
+            .find_any(|e| e.is_some())
+            .flatten()
+    }
+}
+
+#[cfg(test)]
+extern crate std;
+
+#[test]
+fn try_from_str_empty() {
+    assert_eq!(DynPattern::try_from_str("").unwrap_err(), PatternError::Empty);
+    assert_eq!(
+        DynPattern::try_from_str("   ").unwrap_err(),
+        PatternError::Empty
+    );
+}
+
+#[test]
+fn try_from_str_bad_group_length() {
+    for bad in ["F", "FFF", "?"] {
+        assert_eq!(
+            DynPattern::try_from_str(bad).unwrap_err(),
+            PatternError::BadGroupLength
+        );
+    }
+}
+
+#[test]
+fn try_from_str_invalid_hex() {
+    assert_eq!(
+        DynPattern::try_from_str("GG").unwrap_err(),
+        PatternError::InvalidHex
+    );
+}
+
+#[test]
+fn try_from_str_mixed_round_trip() {
+    let pat = DynPattern::try_from_str("FF ?? D8").unwrap();
+    assert_eq!(pat.data(), [0xFF, 0x00, 0xD8]);
+    assert_eq!(pat.mask(), [0xFF, 0x00, 0xFF]);
+    assert!(!pat.is_no_mask());
+}
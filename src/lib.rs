@@ -1,7 +1,19 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 // use const_format::concatcp;
+#[cfg(feature = "alloc")]
+extern crate alloc;
 #[cfg(feature = "simd")]
 use wide::u8x16;
+
+#[cfg(feature = "alloc")]
+mod dynamic;
+#[cfg(feature = "alloc")]
+pub use dynamic::{DynPattern, PatternError};
+
+#[cfg(all(feature = "alloc", feature = "memchr"))]
+mod pattern_set;
+#[cfg(all(feature = "alloc", feature = "memchr"))]
+pub use pattern_set::PatternSet;
 /// A byte pattern.
 /// This type needs you to specify the total size of your pattern in order to work
 #[derive(Debug)]
@@ -10,8 +22,38 @@ pub struct Pattern<const SIZE: usize> {
     mask: [u8; SIZE],
     pattern_i: usize,
     no_mask: bool,
+    /// The rarest concrete (non-wildcard) byte in the pattern, used to drive the
+    /// [`memchr`]-based prefilter in [`Pattern::search`]
+    rare_byte: u8,
+    /// Offset of `rare_byte` within the pattern
+    rare_off: usize,
+    /// Whether the pattern has at least one concrete byte to prefilter on
+    has_rare: bool,
 }
 
+/// Relative commonness of each byte value in typical binaries (higher = more common).
+///
+/// Used to pick the rarest concrete byte in a masked pattern, so [`Pattern::search`] can
+/// prefilter candidate offsets with [`memchr`] instead of testing every window.
+const BYTE_FREQUENCIES: [u8; 256] = [
+    255, 150, 150, 150, 150, 40, 40, 40, 150, 40, 40, 40, 40, 40, 40, 40,
+    150, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40,
+    120, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 120, 120, 120, 120,
+    120, 120, 120, 120, 120, 120, 120, 120, 120, 120, 120, 40, 40, 40, 40, 40,
+    40, 120, 120, 120, 120, 120, 120, 120, 120, 120, 120, 120, 120, 120, 120, 120,
+    120, 120, 120, 120, 120, 120, 120, 120, 120, 120, 120, 40, 120, 40, 40, 120,
+    40, 120, 120, 120, 120, 120, 120, 120, 120, 120, 120, 120, 120, 120, 120, 120,
+    120, 120, 120, 120, 120, 120, 120, 120, 120, 120, 120, 40, 40, 40, 40, 40,
+    40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40,
+    150, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40,
+    40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40,
+    40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40,
+    40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40,
+    40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40,
+    40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40,
+    40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 200,
+];
+
 macro_rules! const_unwrap {
     ($call:expr, $message:literal) => {
         match $call {
@@ -81,11 +123,15 @@ impl<const SIZE: usize> Pattern<SIZE> {
                 pattern_i += 1;
             }
         }
+        let (rare_byte, rare_off, has_rare) = pick_rare_byte_array(&data, &mask, pattern_i);
         Self {
             data,
             mask,
             pattern_i,
             no_mask,
+            rare_byte,
+            rare_off,
+            has_rare,
         }
     }
 
@@ -96,20 +142,74 @@ impl<const SIZE: usize> Pattern<SIZE> {
         if self.no_mask {
             return memchr::memmem::find(bytes, &self.data[..self.pattern_i]);
         }
-        'search: for (i, slice) in bytes.windows(self.pattern_i).enumerate() {
-            'compare: for index in 0..self.pattern_i {
-                if self.mask[index] == 0 {
-                    continue 'compare;
-                }
-                if self.data[index] != slice[index] {
-                    continue 'search;
-                }
+        #[cfg(feature = "memchr")]
+        if self.has_rare {
+            return self.search_with_prefilter(bytes);
+        }
+        bytes
+            .windows(self.pattern_i)
+            .position(|slice| self.matches_slice(slice))
+    }
+
+    /// Search using a rare-byte prefilter: only verify candidates where `rare_byte` occurs at
+    /// its expected offset, instead of testing every window start
+    #[cfg(feature = "memchr")]
+    fn search_with_prefilter(&self, bytes: &[u8]) -> Option<usize> {
+        for h in memchr::memchr_iter(self.rare_byte, bytes) {
+            if h < self.rare_off {
+                continue;
+            }
+            let start = h - self.rare_off;
+            if start + self.pattern_i > bytes.len() {
+                continue;
+            }
+            if self.matches_slice(&bytes[start..start + self.pattern_i]) {
+                return Some(start);
             }
-            return Some(i);
         }
         None
     }
 
+    /// Check whether a window the size of this pattern matches, honouring the mask
+    fn matches_slice(&self, slice: &[u8]) -> bool {
+        matches_masked(&self.data[..self.pattern_i], &self.mask[..self.pattern_i], slice)
+    }
+
+    /// Search pattern inside bytes, returning the offset of the *last* match
+    ///
+    /// Scans windows from the end, honouring the mask just like [`Pattern::search`]. Useful for
+    /// locating the final occurrence of a signature, e.g. a trailing record in a patched binary.
+    pub fn rsearch(&self, bytes: &[u8]) -> Option<usize> {
+        assert!(self.pattern_i <= SIZE);
+        #[cfg(feature = "memchr")]
+        if self.no_mask {
+            return memchr::memmem::rfind(bytes, &self.data[..self.pattern_i]);
+        }
+        bytes
+            .windows(self.pattern_i)
+            .enumerate()
+            .rev()
+            .find(|(_, slice)| self.matches_slice(slice))
+            .map(|(i, _)| i)
+    }
+
+    /// Search pattern inside bytes, yielding every offset where it matches
+    ///
+    /// Matches may overlap: after a hit the scan resumes one byte past the start of that hit.
+    pub fn search_all<'a>(&'a self, bytes: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        assert!(self.pattern_i <= SIZE);
+        bytes
+            .windows(self.pattern_i)
+            .enumerate()
+            .filter(move |(_, slice)| self.matches_slice(slice))
+            .map(|(i, _)| i)
+    }
+
+    /// Count how many times the pattern matches inside bytes
+    pub fn count_matches(&self, bytes: &[u8]) -> usize {
+        self.search_all(bytes).count()
+    }
+
     /// Search pattern inside bytes with SIMD
     #[inline(never)]
     #[cfg(feature = "simd")]
@@ -119,37 +219,68 @@ impl<const SIZE: usize> Pattern<SIZE> {
         if self.no_mask {
             return memchr::memmem::find(bytes, &self.data[..self.pattern_i]);
         }
+        #[cfg(feature = "memchr")]
+        if self.has_rare {
+            return self.simd_search_with_prefilter(bytes);
+        }
+        bytes
+            .windows(self.pattern_i)
+            .position(|slice| self.simd_matches_slice(slice))
+    }
+
+    /// Search using the rare-byte prefilter, verifying each candidate with SIMD instead of
+    /// testing every window start
+    #[cfg(all(feature = "simd", feature = "memchr"))]
+    fn simd_search_with_prefilter(&self, bytes: &[u8]) -> Option<usize> {
+        for h in memchr::memchr_iter(self.rare_byte, bytes) {
+            if h < self.rare_off {
+                continue;
+            }
+            let start = h - self.rare_off;
+            if start + self.pattern_i > bytes.len() {
+                continue;
+            }
+            if self.simd_matches_slice(&bytes[start..start + self.pattern_i]) {
+                return Some(start);
+            }
+        }
+        None
+    }
+
+    /// Check whether a window the size of this pattern matches, honouring the mask, with SIMD
+    #[cfg(feature = "simd")]
+    fn simd_matches_slice(&self, slice: &[u8]) -> bool {
         let mut pattern_chunks = self.data[..self.pattern_i].chunks_exact(16);
         let mut mask_chunks = self.mask[..self.pattern_i].chunks_exact(16);
-        'search: for (i, slice) in bytes.windows(self.pattern_i).enumerate() {
-            let slice_chunks = slice.chunks_exact(16);
-            let mut pchunks = pattern_chunks.clone();
-            let mut mchunks = mask_chunks.clone();
-            for chunk in slice_chunks {
-                let chunk = u8x16::new(chunk.try_into().unwrap());
-                let pattern_chunk = u8x16::new(pchunks.next()?.try_into().unwrap());
-                let mask_chunk = u8x16::new(mchunks.next()?.try_into().unwrap());
-                let masked = chunk & mask_chunk;
-                if masked != pattern_chunk {
-                    continue 'search;
-                }
+        for chunk in slice.chunks_exact(16) {
+            let Some(pattern_chunk) = pattern_chunks.next() else {
+                return false;
+            };
+            let Some(mask_chunk) = mask_chunks.next() else {
+                return false;
+            };
+            let chunk = u8x16::new(chunk.try_into().unwrap());
+            let pattern_chunk = u8x16::new(pattern_chunk.try_into().unwrap());
+            let mask_chunk = u8x16::new(mask_chunk.try_into().unwrap());
+            let masked = chunk & mask_chunk;
+            if masked != pattern_chunk {
+                return false;
             }
-            // println!("got to rem");
-            let rem_chunk = slice.chunks_exact(16).remainder();
-            let rem_start = slice.len() - rem_chunk.len();
-            assert!(rem_chunk.len() + rem_start <= SIZE);
-            'remainder: for (i, byte) in rem_chunk.iter().enumerate() {
-                if self.mask[rem_start + i] == 0 {
-                    continue 'remainder;
-                }
-                if self.data[rem_start + i] != *byte {
-                    continue 'search;
-                }
+        }
+        let rem_chunk = slice.chunks_exact(16).remainder();
+        let rem_start = slice.len() - rem_chunk.len();
+        assert!(rem_chunk.len() + rem_start <= SIZE);
+        for (i, byte) in rem_chunk.iter().enumerate() {
+            if self.mask[rem_start + i] == 0 {
+                continue;
+            }
+            if self.data[rem_start + i] != *byte {
+                return false;
             }
-            return Some(i);
         }
-        None
+        true
     }
+
     /// Search pattern inside bytes with multiple threads
     #[cfg(feature = "multithreading")]
     pub fn par_search(&self, bytes: &[u8]) -> Option<usize> {
@@ -158,25 +289,93 @@ impl<const SIZE: usize> Pattern<SIZE> {
         if self.no_mask {
             return memchr::memmem::find(bytes, &self.data[..self.pattern_i]);
         }
-        use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+        #[cfg(all(feature = "memchr", feature = "alloc"))]
+        if self.has_rare {
+            return self.par_search_with_prefilter(bytes);
+        }
+        use rayon::iter::{IndexedParallelIterator, ParallelIterator};
         use rayon::slice::ParallelSlice;
-        let gah = bytes
+        bytes
             .par_windows(self.pattern_i)
             .enumerate()
-            .map(|(i, slice)| {
-                'compare: for index in 0..self.pattern_i {
-                    if self.mask[index] == 0 {
-                        continue 'compare;
-                    }
-                    if self.data[index] != slice[index] {
-                        return None;
-                    }
+            .filter_map(|(i, slice)| self.matches_slice(slice).then_some(i))
+            .find_any(|_| true)
+    }
+
+    /// Search using the rare-byte prefilter, verifying only the candidates `rare_byte` turns up
+    /// in parallel, instead of every window start
+    #[cfg(all(feature = "multithreading", feature = "memchr", feature = "alloc"))]
+    fn par_search_with_prefilter(&self, bytes: &[u8]) -> Option<usize> {
+        use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+        let hits: alloc::vec::Vec<usize> = memchr::memchr_iter(self.rare_byte, bytes).collect();
+        hits.par_iter()
+            .filter_map(|&h| {
+                if h < self.rare_off {
+                    return None;
+                }
+                let start = h - self.rare_off;
+                if start + self.pattern_i > bytes.len() {
+                    return None;
                 }
-                Some(i)
+                self.matches_slice(&bytes[start..start + self.pattern_i])
+                    .then_some(start)
             })
-            .find_any(|e| e.is_some());
-        gah.flatten()
+            .find_any(|_| true)
+    }
+}
+
+/// Pick the concrete (non-wildcard) byte with the lowest entry in [`BYTE_FREQUENCIES`]
+///
+/// Returns the byte value, its offset within `data`/`mask`, and whether a concrete byte was
+/// found at all (an all-wildcard pattern has none). Used by `DynPattern::try_from_str`; const
+/// patterns use [`pick_rare_byte_array`] instead since range-slicing isn't allowed in a const fn.
+fn pick_rare_byte(data: &[u8], mask: &[u8]) -> (u8, usize, bool) {
+    pick_rare_byte_impl(data, mask, mask.len())
+}
+
+/// Same selection as [`pick_rare_byte`], but over a fixed-size array truncated to `pattern_i`,
+/// so it can run inside the const fn [`Pattern::from_str`]
+const fn pick_rare_byte_array<const SIZE: usize>(
+    data: &[u8; SIZE],
+    mask: &[u8; SIZE],
+    pattern_i: usize,
+) -> (u8, usize, bool) {
+    pick_rare_byte_impl(data, mask, pattern_i)
+}
+
+/// Shared scan used by [`pick_rare_byte`] and [`pick_rare_byte_array`]
+const fn pick_rare_byte_impl(data: &[u8], mask: &[u8], pattern_i: usize) -> (u8, usize, bool) {
+    let mut best_off = 0;
+    let mut best_score = 256u16;
+    let mut found = false;
+    let mut i = 0;
+    while i < pattern_i {
+        if mask[i] != 0 {
+            let score = BYTE_FREQUENCIES[data[i] as usize] as u16;
+            if !found || score < best_score {
+                best_score = score;
+                best_off = i;
+                found = true;
+            }
+        }
+        i += 1;
     }
+    (data[best_off], best_off, found)
+}
+
+/// Check whether `window` matches `data` under `mask`, skipping wildcard positions
+///
+/// Shared between [`Pattern`] and `DynPattern` so wildcards behave identically everywhere.
+fn matches_masked(data: &[u8], mask: &[u8], window: &[u8]) -> bool {
+    for index in 0..data.len() {
+        if mask[index] == 0 {
+            continue;
+        }
+        if data[index] != window[index] {
+            return false;
+        }
+    }
+    true
 }
 
 const fn get_pattern_size(pattern: &str) -> usize {
@@ -218,3 +417,20 @@ fn mt() {
     let sus = std::fs::read("libminecraftpe.so").unwrap();
     assert_eq!(pat.par_search(&sus).unwrap(), 100932284);
 }
+#[test]
+fn search_all_overlapping() {
+    let needle: Pattern<3> = Pattern::from_str("AA AA");
+    assert!(needle.search_all(&[0xAA, 0xAA, 0xAA]).eq([0, 1]));
+    assert_eq!(needle.count_matches(&[0xAA, 0xAA, 0xAA]), 2);
+}
+#[test]
+fn search_all_wildcard_matches_search() {
+    let needle: Pattern<3> = Pattern::from_str("?? BB");
+    let bytes = [0x11, 0x22, 0xBB];
+    assert_eq!(needle.search_all(&bytes).next(), needle.search(&bytes));
+}
+#[test]
+fn search_all_no_match() {
+    let needle: Pattern<2> = Pattern::from_str("FF");
+    assert_eq!(needle.count_matches(&[0x00, 0x11, 0x22]), 0);
+}
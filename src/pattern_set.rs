@@ -0,0 +1,144 @@
+//! Scanning a haystack for many patterns in one pass instead of one at a time
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::{matches_masked, DynPattern};
+
+/// A collection of [`DynPattern`]s scanned together against one haystack, much cheaper than
+/// calling [`DynPattern::search`] once per pattern
+pub struct PatternSet {
+    patterns: Vec<DynPattern>,
+    #[cfg(feature = "aho-corasick")]
+    concrete_indices: Vec<usize>,
+    masked_by_rare_byte: BTreeMap<u8, Vec<usize>>,
+    no_rare_indices: Vec<usize>,
+}
+
+impl PatternSet {
+    /// Build a set from already-parsed patterns, grouping them for efficient joint scanning
+    pub fn new(patterns: Vec<DynPattern>) -> Self {
+        #[cfg(feature = "aho-corasick")]
+        let mut concrete_indices = Vec::new();
+        let mut masked_by_rare_byte: BTreeMap<u8, Vec<usize>> = BTreeMap::new();
+        let mut no_rare_indices = Vec::new();
+        for (i, pattern) in patterns.iter().enumerate() {
+            #[cfg(feature = "aho-corasick")]
+            if pattern.is_no_mask() {
+                concrete_indices.push(i);
+                continue;
+            }
+            if pattern.has_rare() {
+                masked_by_rare_byte
+                    .entry(pattern.rare_byte())
+                    .or_default()
+                    .push(i);
+            } else {
+                no_rare_indices.push(i);
+            }
+        }
+        Self {
+            patterns,
+            #[cfg(feature = "aho-corasick")]
+            concrete_indices,
+            masked_by_rare_byte,
+            no_rare_indices,
+        }
+    }
+
+    /// Number of patterns in the set
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    /// Whether the set has no patterns
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Scan the haystack for every pattern in the set, returning the first match offset of each
+    /// pattern that matched, as `(pattern_index, offset)`
+    pub fn scan(&self, bytes: &[u8]) -> Vec<(usize, usize)> {
+        let mut found: Vec<Option<usize>> = alloc::vec![None; self.patterns.len()];
+
+        #[cfg(feature = "aho-corasick")]
+        if !self.concrete_indices.is_empty() {
+            if let Ok(ac) = aho_corasick::AhoCorasick::new(
+                self.concrete_indices
+                    .iter()
+                    .map(|&i| self.patterns[i].data()),
+            ) {
+                for mat in ac.find_overlapping_iter(bytes) {
+                    let idx = self.concrete_indices[mat.pattern().as_usize()];
+                    found[idx].get_or_insert(mat.start());
+                }
+            }
+        }
+
+        for (&rare_byte, indices) in &self.masked_by_rare_byte {
+            for h in memchr::memchr_iter(rare_byte, bytes) {
+                for &idx in indices {
+                    if found[idx].is_some() {
+                        continue;
+                    }
+                    let pattern = &self.patterns[idx];
+                    let Some(start) = h.checked_sub(pattern.rare_off()) else {
+                        continue;
+                    };
+                    if start + pattern.data().len() > bytes.len() {
+                        continue;
+                    }
+                    let window = &bytes[start..start + pattern.data().len()];
+                    if matches_masked(pattern.data(), pattern.mask(), window) {
+                        found[idx] = Some(start);
+                    }
+                }
+            }
+        }
+
+        for &idx in &self.no_rare_indices {
+            found[idx] = self.patterns[idx].search(bytes);
+        }
+
+        found
+            .into_iter()
+            .enumerate()
+            .filter_map(|(idx, start)| start.map(|start| (idx, start)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+fn dyn_pat(s: &str) -> DynPattern {
+    DynPattern::try_from_str(s).unwrap()
+}
+
+#[test]
+fn scan_overlapping_concrete_patterns() {
+    let set = PatternSet::new(alloc::vec![dyn_pat("61 62 61 62"), dyn_pat("62 61 62")]);
+    let found = set.scan(b"ababab");
+    assert_eq!(found.len(), 2);
+    assert!(found.contains(&(0, 0)));
+    assert!(found.contains(&(1, 1)));
+}
+
+#[test]
+fn scan_masked_patterns_sharing_rare_byte() {
+    let set = PatternSet::new(alloc::vec![dyn_pat("AA ?? FF"), dyn_pat("AA ?? 00")]);
+    let found = set.scan(&[0x00, 0xAA, 0x11, 0xFF, 0xAA, 0x22, 0x00]);
+    assert_eq!(found.len(), 2);
+    assert!(found.contains(&(0, 1)));
+    assert!(found.contains(&(1, 4)));
+}
+
+#[test]
+fn scan_all_wildcard_matches_anywhere() {
+    let set = PatternSet::new(alloc::vec![dyn_pat("?? ??")]);
+    let found = set.scan(&[0x01, 0x02, 0x03]);
+    assert_eq!(found, alloc::vec![(0, 0)]);
+}
+
+#[test]
+fn scan_no_match() {
+    let set = PatternSet::new(alloc::vec![dyn_pat("FF FF")]);
+    assert!(set.scan(&[0x00, 0x11, 0x22]).is_empty());
+}